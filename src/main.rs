@@ -1,16 +1,16 @@
 use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
 use std::{
     fs,
-    io::Write,
     path::{Path, PathBuf},
     str,
     sync::{Arc, Mutex},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufWriter},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     net::UnixListener,
-    time,
+    sync::{mpsc, watch},
 };
 use tokio::{net::UnixStream, process};
 
@@ -29,6 +29,10 @@ enum Subapp {
 
         /// Directory of wallpaper images
         directory: PathBuf,
+
+        /// Don't spawn swww or run swww img; just log what would happen
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Pause wallpaper switching
@@ -39,6 +43,16 @@ enum Subapp {
 
     /// Kills the daemon
     Kill,
+
+    /// Reports the daemon's current status
+    Status,
+
+    /// Switches to the next wallpaper now: the entry ahead in history if
+    /// `prev` was used, else a new pick. While paused, waits for unpause.
+    Next,
+
+    /// Steps back to the previously displayed wallpaper
+    Prev,
 }
 
 struct DropUnixListener {
@@ -74,30 +88,167 @@ struct App {
     command: Subapp,
 }
 
+/// Maximum number of wallpapers kept in `SharedState::history`.
+const HISTORY_CAP: usize = 100;
+
+/// State shared between `listen_loop` and `set_loop`, guarded by a single
+/// mutex so a `status` query always observes a consistent snapshot. The
+/// pause flag itself lives in a `watch` channel instead, since `set_loop`
+/// needs to await changes to it rather than poll it.
+struct SharedState {
+    current: Option<PathBuf>,
+    next_switch: Instant,
+    /// Wallpapers shown so far, oldest first, capped at `HISTORY_CAP`.
+    history: Vec<PathBuf>,
+    /// Index into `history` of the wallpaper currently on screen.
+    cursor: usize,
+    /// Shuffled order in which wallpapers are handed out, indices into the
+    /// daemon's file list.
+    playlist: Vec<usize>,
+    /// Position of the next unserved entry in `playlist`.
+    playlist_pos: usize,
+}
+
+impl SharedState {
+    /// Records a freshly-picked wallpaper at the head of the history,
+    /// dropping the oldest entry once `HISTORY_CAP` is exceeded.
+    fn push_history(&mut self, img: PathBuf) {
+        self.history.push(img);
+        if self.history.len() > HISTORY_CAP {
+            self.history.remove(0);
+        }
+        self.cursor = self.history.len() - 1;
+    }
+
+    /// Hands out the next index into the file list, reshuffling the
+    /// playlist once it's exhausted. The reshuffle is nudged so its first
+    /// entry never repeats the last wallpaper served before the reshuffle.
+    fn next_playlist_index(&mut self, total: usize) -> usize {
+        if self.playlist_pos >= self.playlist.len() {
+            let last_served = self.playlist.last().copied();
+            let mut shuffled = shuffled_indices(total);
+            if shuffled.len() > 1 && shuffled.first() == last_served.as_ref() {
+                shuffled.swap(0, 1);
+            }
+            self.playlist = shuffled;
+            self.playlist_pos = 0;
+        }
+        let idx = self.playlist[self.playlist_pos];
+        self.playlist_pos += 1;
+        idx
+    }
+}
+
+/// Fisher–Yates shuffle of `0..len`.
+fn shuffled_indices(len: usize) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..len).collect();
+    for i in (1..len).rev() {
+        let j = rand::random::<usize>() % (i + 1);
+        indices.swap(i, j);
+    }
+    indices
+}
+
+type State = Arc<Mutex<SharedState>>;
+
+/// Wire commands sent from the CLI to the daemon over the control socket,
+/// framed as length-prefixed MessagePack.
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Pause,
+    Unpause,
+    Kill,
+    Next,
+    Prev,
+    Status,
+    SetInterval(u64),
+}
+
+/// Wire replies sent back from the daemon for a [`Request`].
+#[derive(Serialize, Deserialize)]
+enum Response {
+    Ok,
+    State {
+        paused: bool,
+        current: Option<PathBuf>,
+        secs_to_next: u64,
+    },
+    Error(String),
+}
+
+async fn write_frame<W: AsyncWrite + Unpin, T: Serialize>(writer: &mut W, value: &T) -> Result<()> {
+    let bytes = rmp_serde::to_vec(value)?;
+    writer.write_u32(bytes.len() as u32).await?;
+    writer.write_all(&bytes).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Upper bound on a single frame's body size, so a garbage or hostile length
+/// prefix can't be used to force a multi-GB allocation; our largest real
+/// message (a `Response::State`) is a few dozen bytes.
+const MAX_FRAME_LEN: u32 = 1 << 20;
+
+async fn read_frame<R: AsyncRead + Unpin, T: for<'de> Deserialize<'de>>(reader: &mut R) -> Result<T> {
+    let len = reader.read_u32().await?;
+    if len > MAX_FRAME_LEN {
+        bail!("frame length {len} exceeds max of {MAX_FRAME_LEN}");
+    }
+    let mut buf = vec![0u8; len as usize];
+    reader.read_exact(&mut buf).await?;
+    Ok(rmp_serde::from_slice(&buf)?)
+}
+
 #[tokio::main]
 async fn main() {
     let app = App::parse();
     match app.command {
         Subapp::Pause => {
-            if let Err(e) = send("pause").await {
+            if let Err(e) = send(Request::Pause).await {
                 eprintln!("Failed to pause wallpaper switching: {}", e)
             }
         }
         Subapp::Unpause => {
-            if let Err(e) = send("unpause").await {
+            if let Err(e) = send(Request::Unpause).await {
                 eprintln!("Failed to unpause wallpaper switching: {}", e)
             }
         }
         Subapp::Kill => {
-            if let Err(e) = send("kill").await {
+            if let Err(e) = send(Request::Kill).await {
                 eprintln!("Failed to kill daemon: {}", e)
             }
         }
+        Subapp::Next => {
+            if let Err(e) = send(Request::Next).await {
+                eprintln!("Failed to switch wallpaper: {}", e)
+            }
+        }
+        Subapp::Prev => {
+            if let Err(e) = send(Request::Prev).await {
+                eprintln!("Failed to switch wallpaper: {}", e)
+            }
+        }
+        Subapp::Status => match send(Request::Status).await {
+            Ok(Response::State {
+                paused,
+                current,
+                secs_to_next,
+            }) => {
+                let current = current
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "none".to_string());
+                println!("paused={paused} current={current} secs_to_next={secs_to_next}");
+            }
+            Ok(Response::Error(e)) => eprintln!("Daemon reported an error: {e}"),
+            Ok(Response::Ok) => eprintln!("Daemon sent an unexpected reply to status"),
+            Err(e) => eprintln!("Failed to query daemon status: {}", e),
+        },
         Subapp::Daemon {
             interval,
             directory,
+            dry_run,
         } => {
-            if let Err(e) = init(&directory, interval.unwrap_or(60)).await {
+            if let Err(e) = init(&directory, interval.unwrap_or(60), dry_run).await {
                 eprintln!("Daemon error: {}", e)
             }
         }
@@ -109,51 +260,184 @@ fn get_socket_location() -> Result<PathBuf> {
     return Ok(runtime_dir.join(Path::new(SOCKET_NAME)));
 }
 
-async fn set_loop(files: Vec<PathBuf>, paused: Arc<Mutex<bool>>, interval: Duration) -> Result<()> {
+/// Runs `swww img` against `path`, logging success or failure the way the
+/// old inline call in `set_loop` did. Shared by the timer path and the
+/// `prev`/`next` command paths so there's one place that shells out. In
+/// `dry_run` mode it just logs the path it would have set.
+async fn set_wallpaper(path: &Path, dry_run: bool) -> Result<bool> {
+    if dry_run {
+        println!("[dry-run] would set wallpaper to {}", path.to_str().unwrap());
+        return Ok(true);
+    }
+    let out = process::Command::new("swww")
+        .args(["img", "--transition-type", "fade", path.to_str().unwrap()])
+        .output()
+        .await?;
+    if !out.status.success() {
+        eprintln!(
+            "swww img {} FAILED, {}",
+            path.to_str().unwrap(),
+            str::from_utf8(&out.stderr).unwrap()
+        );
+        Ok(false)
+    } else {
+        println!("swww img {} SUCCESS", path.to_str().unwrap());
+        Ok(true)
+    }
+}
+
+/// Which way a manual skip (`next`/`prev`) should move; sent from
+/// `listen_loop` to `set_loop` over `skip_tx` so a single task ever mutates
+/// `history`/`cursor` or shells out to `swww`.
+enum SkipDirection {
+    Next,
+    Prev,
+}
+
+/// Moves forward one step: if the cursor had been walked back through
+/// history, re-displays the entry ahead of it; otherwise picks a fresh
+/// random wallpaper and appends it to the history.
+async fn advance(files: &[PathBuf], state: &State, dry_run: bool) -> Result<()> {
+    let img = {
+        let mut guard = state.lock().unwrap();
+        if guard.cursor + 1 < guard.history.len() {
+            guard.cursor += 1;
+            guard.history[guard.cursor].clone()
+        } else {
+            let idx = guard.next_playlist_index(files.len());
+            let img = files[idx].clone();
+            guard.push_history(img.clone());
+            img
+        }
+    };
+    if set_wallpaper(&img, dry_run).await? {
+        state.lock().unwrap().current = Some(img);
+    }
+    Ok(())
+}
+
+/// Steps the cursor back one entry in history and re-displays it, if there
+/// is an earlier entry to go to.
+async fn retreat(state: &State, dry_run: bool) -> Result<()> {
+    let img = {
+        let mut guard = state.lock().unwrap();
+        if guard.history.is_empty() || guard.cursor == 0 {
+            None
+        } else {
+            guard.cursor -= 1;
+            Some(guard.history[guard.cursor].clone())
+        }
+    };
+    let Some(img) = img else {
+        eprintln!("No earlier wallpaper in history");
+        return Ok(());
+    };
+    if set_wallpaper(&img, dry_run).await? {
+        state.lock().unwrap().current = Some(img);
+    }
+    Ok(())
+}
+
+async fn set_loop(
+    files: Vec<PathBuf>,
+    state: State,
+    mut paused_rx: watch::Receiver<bool>,
+    mut skip_rx: mpsc::UnboundedReceiver<SkipDirection>,
+    interval: Duration,
+    dry_run: bool,
+) -> Result<()> {
     tokio::time::sleep(Duration::from_secs(5)).await;
     let mut ticker = tokio::time::interval(interval);
     loop {
-        ticker.tick().await;
-        if paused.lock().is_ok_and(|p| *p) {
-            continue;
-        }
-        let idx = rand::random::<usize>() % files.len();
-        let img = files.get(idx).unwrap();
-        let out = process::Command::new("swww")
-            .args(["img", "--transition-type", "fade", img.to_str().unwrap()])
-            .output()
-            .await?;
-        if !out.status.success() {
-            eprintln!(
-                "swww img {} FAILED, {}",
-                img.to_str().unwrap(),
-                str::from_utf8(&out.stderr).unwrap()
-            );
-        } else {
-            println!("swww img {} SUCCESS", img.to_str().unwrap(),);
+        let direction = tokio::select! {
+            _ = ticker.tick() => SkipDirection::Next,
+            dir = skip_rx.recv() => {
+                ticker.reset();
+                dir.ok_or_else(|| anyhow::anyhow!("skip channel closed"))?
+            }
+        };
+        while *paused_rx.borrow() {
+            paused_rx.changed().await?;
         }
+        state.lock().unwrap().next_switch = Instant::now() + interval;
+        match direction {
+            SkipDirection::Next => advance(&files, &state, dry_run).await?,
+            SkipDirection::Prev => retreat(&state, dry_run).await?,
+        }
+    }
+}
+
+/// Builds the [`Response::State`] answer to a `status` query.
+fn status_response(state: &SharedState, paused: bool) -> Response {
+    Response::State {
+        paused,
+        current: state.current.clone(),
+        secs_to_next: state
+            .next_switch
+            .saturating_duration_since(Instant::now())
+            .as_secs(),
     }
 }
 
-async fn listen_loop(paused: Arc<Mutex<bool>>) -> Result<()> {
+/// Reads one request off `stream`, dispatches it, and writes back the
+/// response. Returns whether the daemon should shut down. Any error here
+/// (e.g. a client that connects and disconnects without writing a full
+/// frame) is scoped to this single connection by the caller.
+async fn handle_connection(
+    stream: UnixStream,
+    state: &State,
+    paused_tx: &watch::Sender<bool>,
+    skip_tx: &mpsc::UnboundedSender<SkipDirection>,
+) -> Result<bool> {
+    let (mut read_half, mut write_half) = stream.into_split();
+    let request: Request = read_frame(&mut read_half).await?;
+    let (response, should_stop) = match request {
+        Request::Pause => {
+            paused_tx.send(true)?;
+            (Response::Ok, false)
+        }
+        Request::Unpause => {
+            paused_tx.send(false)?;
+            (Response::Ok, false)
+        }
+        Request::Next => {
+            let _ = skip_tx.send(SkipDirection::Next);
+            (Response::Ok, false)
+        }
+        Request::Prev => {
+            let _ = skip_tx.send(SkipDirection::Prev);
+            (Response::Ok, false)
+        }
+        Request::Status => (
+            status_response(&state.lock().unwrap(), *paused_tx.borrow()),
+            false,
+        ),
+        Request::Kill => (Response::Ok, true),
+        Request::SetInterval(_) => (Response::Error("not yet supported".to_string()), false),
+    };
+    write_frame(&mut write_half, &response).await?;
+    Ok(should_stop)
+}
+
+async fn listen_loop(
+    state: State,
+    paused_tx: watch::Sender<bool>,
+    skip_tx: mpsc::UnboundedSender<SkipDirection>,
+) -> Result<()> {
     let socket_path = get_socket_location()?;
     let socket = DropUnixListener::bind(&socket_path)?;
     loop {
         let (stream, _) = socket.listener.accept().await?;
-        let mut reader = tokio::io::BufReader::new(stream);
-        let mut buf = String::new();
-        reader.read_line(&mut buf).await?;
-        match buf.as_str().trim() {
-            "pause" => *paused.lock().unwrap() = true,
-            "unpause" => *paused.lock().unwrap() = false,
-            "kill" => break,
-            s => eprintln!("Unknown message received: {s}"),
+        match handle_connection(stream, &state, &paused_tx, &skip_tx).await {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(e) => eprintln!("dropping bad control connection: {e}"),
         }
     }
     Ok(())
 }
 
-async fn init(dir: &PathBuf, interval: usize) -> Result<()> {
+async fn init(dir: &PathBuf, interval: usize, dry_run: bool) -> Result<()> {
     let sigint_handler = tokio::spawn(async {
         tokio::signal::ctrl_c()
             .await
@@ -166,45 +450,63 @@ async fn init(dir: &PathBuf, interval: usize) -> Result<()> {
         stream.recv().await;
         println!("Program received SIGTERM, exiting");
     });
-    let mut daemon_cmd = process::Command::new("swww-daemon")
-        .kill_on_drop(true)
-        .spawn()?;
+    let mut daemon_cmd = if dry_run {
+        None
+    } else {
+        Some(
+            process::Command::new("swww-daemon")
+                .kill_on_drop(true)
+                .spawn()?,
+        )
+    };
     let files = fs::read_dir(dir)?
         .filter_map(Result::ok)
         .map(|e| e.path())
         .filter(|p| p.is_file())
         .collect::<Vec<_>>();
-    let paused = Arc::new(Mutex::new(false));
-    let listen_task = tokio::spawn(listen_loop(paused.clone()));
+    if files.is_empty() {
+        bail!("no wallpaper images found in {}", dir.display());
+    }
+    let state = Arc::new(Mutex::new(SharedState {
+        current: None,
+        next_switch: Instant::now() + Duration::from_secs(interval as u64),
+        history: Vec::new(),
+        cursor: 0,
+        playlist: shuffled_indices(files.len()),
+        playlist_pos: 0,
+    }));
+    let (paused_tx, paused_rx) = watch::channel(false);
+    let (skip_tx, skip_rx) = mpsc::unbounded_channel();
+    let listen_task = tokio::spawn(listen_loop(state.clone(), paused_tx, skip_tx));
     let set_task = tokio::spawn(set_loop(
         files,
-        paused.clone(),
+        state.clone(),
+        paused_rx,
+        skip_rx,
         Duration::from_secs(interval as u64),
+        dry_run,
     ));
-    let mut ticker = time::interval(Duration::from_millis(100));
-    loop {
-        ticker.tick().await;
-        if sigterm_handler.is_finished() || sigint_handler.is_finished() {
-            return Ok(());
+    let daemon_wait = async {
+        match &mut daemon_cmd {
+            Some(child) => child.wait().await,
+            None => std::future::pending().await,
         }
-        if daemon_cmd.try_wait()?.is_some() {
+    };
+    tokio::select! {
+        res = sigint_handler => { res?; Ok(()) }
+        res = sigterm_handler => { res?; Ok(()) }
+        status = daemon_wait => {
+            status?;
             bail!("swww-daemon failed to run/crashed");
         }
-        if listen_task.is_finished() {
-            return listen_task.await?;
-        }
-        if set_task.is_finished() {
-            return set_task.await?;
-        }
+        res = listen_task => res?,
+        res = set_task => res?,
     }
 }
 
-async fn send(msg: &str) -> Result<()> {
+async fn send(request: Request) -> Result<Response> {
     let socket = UnixStream::connect(get_socket_location()?).await?;
-    let mut writer = BufWriter::new(socket);
-    let mut buf = Vec::<u8>::new();
-    writeln!(&mut buf, "{msg}")?;
-    writer.write_all(&buf).await?;
-    writer.flush().await?;
-    Ok(())
+    let (mut read_half, mut write_half) = socket.into_split();
+    write_frame(&mut write_half, &request).await?;
+    read_frame(&mut read_half).await
 }
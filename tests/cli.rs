@@ -0,0 +1,156 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::{fs, path::Path, thread, time::Duration};
+use tempfile::tempdir;
+
+/// Long enough that the background ticker never fires again after its first
+/// tick (at the end of the daemon's 5s startup delay), so a switch observed
+/// after that can only be attributed to the command we just sent.
+const LONG_INTERVAL: &str = "3600";
+
+fn spawn_dry_run_daemon(runtime_dir: &Path, image_dir: &Path, interval: &str) -> std::process::Child {
+    std::process::Command::new(assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")))
+        .arg("daemon")
+        .arg("--dry-run")
+        .arg("--interval")
+        .arg(interval)
+        .arg(image_dir)
+        .env("XDG_RUNTIME_DIR", runtime_dir)
+        .spawn()
+        .expect("failed to spawn daemon")
+}
+
+fn cli(runtime_dir: &Path) -> Command {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.env("XDG_RUNTIME_DIR", runtime_dir);
+    cmd
+}
+
+/// Runs `status` and pulls out the `current=...` field, for tests that need
+/// to compare which wallpaper is showing rather than just substring-match it.
+fn current_wallpaper(runtime_dir: &Path) -> String {
+    let output = std::process::Command::new(assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")))
+        .arg("status")
+        .env("XDG_RUNTIME_DIR", runtime_dir)
+        .output()
+        .expect("failed to run status");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    stdout
+        .split("current=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("status output missing current=")
+        .to_string()
+}
+
+fn write_images(image_dir: &Path, names: &[&str]) {
+    for name in names {
+        fs::write(image_dir.join(name), b"fake").unwrap();
+    }
+}
+
+#[test]
+fn pause_unpause_and_status_round_trip() {
+    let runtime_dir = tempdir().unwrap();
+    let image_dir = tempdir().unwrap();
+    write_images(image_dir.path(), &["one.png", "two.png"]);
+
+    let mut daemon = spawn_dry_run_daemon(runtime_dir.path(), image_dir.path(), LONG_INTERVAL);
+    thread::sleep(Duration::from_millis(500));
+
+    cli(runtime_dir.path()).arg("pause").assert().success();
+    cli(runtime_dir.path())
+        .arg("status")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("paused=true"));
+
+    cli(runtime_dir.path()).arg("unpause").assert().success();
+    cli(runtime_dir.path())
+        .arg("status")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("paused=false"));
+
+    cli(runtime_dir.path()).arg("kill").assert().success();
+    daemon.wait().ok();
+}
+
+#[test]
+fn next_switches_away_from_the_current_wallpaper() {
+    let runtime_dir = tempdir().unwrap();
+    let image_dir = tempdir().unwrap();
+    write_images(image_dir.path(), &["a.png", "b.png", "c.png"]);
+
+    let mut daemon = spawn_dry_run_daemon(runtime_dir.path(), image_dir.path(), LONG_INTERVAL);
+    // set_loop's ticker fires its first (settling) tick right after the
+    // initial 5s delay, regardless of --interval; wait past that so the
+    // picture we see next is attributable only to our explicit `next`.
+    thread::sleep(Duration::from_secs(6));
+
+    let before = current_wallpaper(runtime_dir.path());
+    cli(runtime_dir.path()).arg("next").assert().success();
+    thread::sleep(Duration::from_millis(200));
+    let after = current_wallpaper(runtime_dir.path());
+
+    assert_ne!(before, after, "next should have picked a different wallpaper");
+
+    cli(runtime_dir.path()).arg("kill").assert().success();
+    daemon.wait().ok();
+}
+
+#[test]
+fn prev_navigates_back_through_history() {
+    let runtime_dir = tempdir().unwrap();
+    let image_dir = tempdir().unwrap();
+    write_images(image_dir.path(), &["a.png", "b.png", "c.png"]);
+
+    let mut daemon = spawn_dry_run_daemon(runtime_dir.path(), image_dir.path(), LONG_INTERVAL);
+    thread::sleep(Duration::from_secs(6));
+
+    let first = current_wallpaper(runtime_dir.path());
+    cli(runtime_dir.path()).arg("next").assert().success();
+    thread::sleep(Duration::from_millis(200));
+    let second = current_wallpaper(runtime_dir.path());
+
+    assert_ne!(first, second);
+
+    cli(runtime_dir.path()).arg("prev").assert().success();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(
+        current_wallpaper(runtime_dir.path()),
+        first,
+        "prev should re-display the wallpaper shown before the last next"
+    );
+
+    cli(runtime_dir.path()).arg("kill").assert().success();
+    daemon.wait().ok();
+}
+
+#[test]
+fn shuffle_playlist_never_repeats_immediately() {
+    let runtime_dir = tempdir().unwrap();
+    let image_dir = tempdir().unwrap();
+    write_images(
+        image_dir.path(),
+        &["a.png", "b.png", "c.png", "d.png", "e.png"],
+    );
+
+    let mut daemon = spawn_dry_run_daemon(runtime_dir.path(), image_dir.path(), LONG_INTERVAL);
+    thread::sleep(Duration::from_secs(6));
+
+    let mut previous = current_wallpaper(runtime_dir.path());
+    for _ in 0..15 {
+        cli(runtime_dir.path()).arg("next").assert().success();
+        thread::sleep(Duration::from_millis(100));
+        let current = current_wallpaper(runtime_dir.path());
+        assert_ne!(
+            current, previous,
+            "shuffle playlist repeated a wallpaper back-to-back"
+        );
+        previous = current;
+    }
+
+    cli(runtime_dir.path()).arg("kill").assert().success();
+    daemon.wait().ok();
+}